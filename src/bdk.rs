@@ -0,0 +1,133 @@
+//! BDK-backed implementations of the receiver callback traits.
+//!
+//! Wires `CanBroadcast`, `IsScriptOwned` and `IsOutputKnown` to a `bdk::Wallet` and a broadcast
+//! backend: `BdkReceiver` implements every callback trait that the typestate pipeline in
+//! [`crate::receive`] takes, instead of leaving ownership and broadcast checks to each binding.
+//! The module does not drive the pipeline itself: the caller still steps through its stages and
+//! hands a `BdkReceiver` to each check that takes a callback.
+//!
+//! This module is meant to sit behind an optional `bdk` feature so integrators who don't use BDK
+//! aren't forced to pull in the dependency — wire it up with an optional `bdk` entry in
+//! `Cargo.toml` and `#[cfg(feature = "bdk")] pub mod bdk;` at the crate root alongside whichever
+//! commit adds this crate's manifest; until then, pulling in this module pulls in `bdk`
+//! unconditionally.
+
+use std::{
+	collections::HashSet,
+	sync::{Arc, Mutex},
+};
+
+use bdk::{database::BatchDatabase, Wallet};
+
+use crate::{
+	receive::{CanBroadcast, IsOutputKnown, IsScriptOwned},
+	OutPoint, PayjoinError, ScriptBuf,
+};
+
+/// Tests whether a transaction would be accepted into the mempool, via whatever backend the
+/// integrator already uses to talk to the network (an Electrum/Esplora client, or `bitcoind` RPC).
+pub trait BroadcastBackend: Send + Sync {
+	fn test_mempool_accept(&self, tx: Vec<u8>) -> Result<bool, PayjoinError>;
+}
+
+/// Persists the outpoints the receiver has already processed, so the anti-probing guarantee of
+/// `check_no_inputs_seen_before` survives a restart instead of resetting with the process.
+///
+/// Implement this against whatever storage already backs the receiver — a table alongside the
+/// wallet's own database, a key-value store, or the wallet database itself — rather than relying
+/// on an in-memory set.
+pub trait SeenOutpointStore: Send + Sync {
+	fn contains(&self, outpoint: &OutPoint) -> Result<bool, PayjoinError>;
+	fn insert(&self, outpoint: &OutPoint) -> Result<(), PayjoinError>;
+}
+
+/// An in-memory `SeenOutpointStore`. Fine for tests or a receiver that doesn't need the anti-
+/// probing guarantee to survive a restart; production receivers should supply a persistent
+/// implementation instead via `BdkReceiver::with_seen_outpoint_store`.
+#[derive(Default)]
+pub struct InMemorySeenOutpointStore {
+	seen: Mutex<HashSet<(String, u32)>>,
+}
+
+impl SeenOutpointStore for InMemorySeenOutpointStore {
+	fn contains(&self, outpoint: &OutPoint) -> Result<bool, PayjoinError> {
+		Ok(self.seen.lock().unwrap().contains(&(outpoint.txid.clone(), outpoint.vout)))
+	}
+
+	fn insert(&self, outpoint: &OutPoint) -> Result<(), PayjoinError> {
+		self.seen.lock().unwrap().insert((outpoint.txid.clone(), outpoint.vout));
+		Ok(())
+	}
+}
+
+/// Adapts a BDK `Wallet` into the three receiver callback traits.
+///
+/// `is_owned`/`is_receiver_output` are resolved via the wallet's script-pubkey index (`is_mine`).
+/// `test_mempool_accept` is routed through the supplied `BroadcastBackend`. `is_known` is backed by
+/// a `SeenOutpointStore`: the first time an outpoint is checked it is recorded right there, so the
+/// guarantee is enforced automatically as part of the check rather than depending on the caller to
+/// remember a separate bookkeeping call afterward.
+pub struct BdkReceiver<D: BatchDatabase + Send + Sync> {
+	wallet: Arc<Mutex<Wallet<D>>>,
+	broadcast: Arc<dyn BroadcastBackend>,
+	seen_outpoints: Arc<dyn SeenOutpointStore>,
+}
+
+impl<D: BatchDatabase + Send + Sync> BdkReceiver<D> {
+	/// Builds a receiver backed by an in-memory `SeenOutpointStore`. Use
+	/// `with_seen_outpoint_store` instead if the anti-probing guarantee must survive a restart.
+	pub fn new(wallet: Arc<Mutex<Wallet<D>>>, broadcast: Arc<dyn BroadcastBackend>) -> Self {
+		Self::with_seen_outpoint_store(
+			wallet,
+			broadcast,
+			Arc::new(InMemorySeenOutpointStore::default()),
+		)
+	}
+
+	pub fn with_seen_outpoint_store(
+		wallet: Arc<Mutex<Wallet<D>>>, broadcast: Arc<dyn BroadcastBackend>,
+		seen_outpoints: Arc<dyn SeenOutpointStore>,
+	) -> Self {
+		Self { wallet, broadcast, seen_outpoints }
+	}
+}
+
+impl<D: BatchDatabase + Send + Sync> CanBroadcast for BdkReceiver<D> {
+	fn test_mempool_accept(&self, tx: Vec<u8>) -> Result<bool, PayjoinError> {
+		self.broadcast.test_mempool_accept(tx)
+	}
+}
+
+impl<D: BatchDatabase + Send + Sync> IsScriptOwned for BdkReceiver<D> {
+	fn is_owned(&self, script: Arc<ScriptBuf>) -> Result<bool, PayjoinError> {
+		let wallet = self.wallet.lock().unwrap();
+		wallet
+			.is_mine(&script.internal)
+			.map_err(|e| PayjoinError::UnexpectedError { message: e.to_string() })
+	}
+}
+
+impl<D: BatchDatabase + Send + Sync> IsOutputKnown for BdkReceiver<D> {
+	fn is_known(&self, outpoint: OutPoint) -> Result<bool, PayjoinError> {
+		let already_seen = self.seen_outpoints.contains(&outpoint)?;
+		if !already_seen {
+			self.seen_outpoints.insert(&outpoint)?;
+		}
+		Ok(already_seen)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn in_memory_store_treats_first_sight_as_unseen_and_remembers_it() {
+		let store = InMemorySeenOutpointStore::default();
+		let outpoint = OutPoint { txid: "1".repeat(64), vout: 0 };
+
+		assert!(!store.contains(&outpoint).unwrap(), "an outpoint is unseen before it's inserted");
+		store.insert(&outpoint).unwrap();
+		assert!(store.contains(&outpoint).unwrap(), "an inserted outpoint is seen afterward");
+	}
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// Errors returned across the payjoin-ffi receive (and send) APIs.
+///
+/// Every fallible method maps its underlying `payjoin` crate error onto one of these variants so
+/// that bindings (Kotlin/Swift/Flutter) can match on the reason instead of regex-matching a
+/// stringified message.
+#[derive(Debug)]
+pub enum PayjoinError {
+	/// Arose from handling the sender's request.
+	RequestError { message: String },
+	/// Arose on the server side while servicing a request.
+	ServerError { message: String },
+	/// Arose while validating or building the receiver's proposal.
+	ReceiveError { message: String },
+	/// An error that isn't expected to occur during normal operation.
+	UnexpectedError { message: String },
+	/// No candidate input avoided the unnecessary input heuristic (UIH), or the proposal has more
+	/// than two outputs so selection was not attempted.
+	SelectionError,
+	/// The contributed input value does not cover the value of outputs added via
+	/// `WantsOutputs::contribute_output`/`contribute_outputs`.
+	InsufficientInputAmount { message: String },
+	/// The sender disabled output substitution (`disableoutputsubstitution=true`), so
+	/// `substitute_output_address` is not allowed.
+	OutputSubstitutionDisabled { message: String },
+	/// An `OutPoint` passed to a `contribute_*_input(s)` call was already contributed, or appears
+	/// more than once in the same batch; outpoints are not checked against the inputs already on
+	/// the proposal. Also returned by `WantsOutputs::contribute_outputs` when `outputs` and
+	/// `receiver_owned` differ in length, and by `WantsOutputs::commit_outputs` when a surplus is
+	/// to be routed to a `change_vout` that is not a receiver-owned contributed output.
+	InvalidContributedInput { message: String },
+	/// The fee required to satisfy the requested feerate exceeds the sender's
+	/// `maxadditionalfeecontribution`.
+	FeeTooHigh { message: String },
+}
+
+impl fmt::Display for PayjoinError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			PayjoinError::RequestError { message } => write!(f, "Request error: {message}"),
+			PayjoinError::ServerError { message } => write!(f, "Server error: {message}"),
+			PayjoinError::ReceiveError { message } => write!(f, "Receive error: {message}"),
+			PayjoinError::UnexpectedError { message } => write!(f, "Unexpected error: {message}"),
+			PayjoinError::SelectionError => {
+				write!(
+					f,
+					"No candidate input avoids the unnecessary input heuristic, or the proposal has more than two outputs"
+				)
+			}
+			PayjoinError::InsufficientInputAmount { message } => {
+				write!(f, "Insufficient input amount: {message}")
+			}
+			PayjoinError::OutputSubstitutionDisabled { message } => {
+				write!(f, "Output substitution disabled: {message}")
+			}
+			PayjoinError::InvalidContributedInput { message } => {
+				write!(f, "Invalid contributed input: {message}")
+			}
+			PayjoinError::FeeTooHigh { message } => write!(f, "Fee too high: {message}"),
+		}
+	}
+}
+
+impl std::error::Error for PayjoinError {}
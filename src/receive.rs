@@ -1,8 +1,9 @@
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	sync::{Arc, Mutex, MutexGuard},
 };
 
+use payjoin::bitcoin::psbt::PartiallySignedTransaction as PdkPsbt;
 use payjoin::receive::{
 	MaybeInputsOwned as PdkMaybeInputsOwned, MaybeInputsSeen as PdkMaybeInputsSeen,
 	MaybeMixedInputScripts as PdkMaybeMixedInputScripts, OutputsUnknown as PdkOutputsUnknown,
@@ -265,7 +266,7 @@ impl OutputsUnknown {
 	/// Find which outputs belong to the receiver
 	pub fn identify_receiver_outputs(
 		&self, is_receiver_output: Box<dyn IsScriptOwned>,
-	) -> Result<Arc<PayjoinProposal>, PayjoinError> {
+	) -> Result<Arc<WantsOutputs>, PayjoinError> {
 		let (unknown_outputs, _) = Self::get_unknown_outputs(self);
 		match unknown_outputs.unwrap().identify_receiver_outputs(|output_script| {
 			let res = is_receiver_output
@@ -281,14 +282,135 @@ impl OutputsUnknown {
 	}
 }
 
+/// A checked proposal the receiver may append outputs to before contributing inputs.
+///
+/// This is where UTXO consolidation (add an extra receiver-owned output), batched payments, and
+/// cut-through forwarding (pay a third party out of the same transaction) happen: append whatever
+/// `TxOut`s are needed with `contribute_output`/`contribute_outputs`, then call `commit_outputs`
+/// to hand the surplus of any contributed input value to a receiver-owned change vout and move on
+/// to input contribution.
+pub struct WantsOutputs {
+	internal: Mutex<Option<PdkPayjoinProposal>>,
+	receiver_vouts: Mutex<Vec<u32>>,
+	added_output_value_sat: Mutex<u64>,
+}
+
+impl From<PdkPayjoinProposal> for WantsOutputs {
+	fn from(value: PdkPayjoinProposal) -> Self {
+		WantsOutputs {
+			internal: Mutex::new(Some(value)),
+			receiver_vouts: Mutex::new(Vec::new()),
+			added_output_value_sat: Mutex::new(0),
+		}
+	}
+}
+
+impl WantsOutputs {
+	fn get_proposal_mutex_guard(&self) -> MutexGuard<Option<PdkPayjoinProposal>> {
+		self.internal.lock().unwrap()
+	}
+
+	/// Append `txout` to the proposal PSBT, returning the vout it was assigned.
+	///
+	/// Keeps `unsigned_tx.output` and the PSBT `outputs` vector in sync: every appended output
+	/// also needs a default PSBT output entry, or downstream signers choke on the length mismatch.
+	pub fn contribute_output(&self, txout: TxOut, is_receiver_owned: bool) -> u32 {
+		let mut guard = self.get_proposal_mutex_guard();
+		let proposal = guard.as_mut().unwrap();
+		let psbt = proposal.psbt_mut();
+		let value = txout.value;
+		psbt.unsigned_tx.output.push(txout.into());
+		psbt.outputs.push(Default::default());
+		let vout = (psbt.unsigned_tx.output.len() - 1) as u32;
+		if is_receiver_owned {
+			self.receiver_vouts.lock().unwrap().push(vout);
+		}
+		*self.added_output_value_sat.lock().unwrap() += value;
+		vout
+	}
+
+	/// Append several outputs in one call. Returns the vouts assigned, in the same order as
+	/// `outputs`/`receiver_owned`.
+	///
+	/// `outputs` and `receiver_owned` must be the same length, so every output's ownership flag is
+	/// explicit rather than silently defaulting to `false` for a missing entry.
+	pub fn contribute_outputs(
+		&self, outputs: Vec<TxOut>, receiver_owned: Vec<bool>,
+	) -> Result<Vec<u32>, PayjoinError> {
+		if outputs.len() != receiver_owned.len() {
+			return Err(PayjoinError::InvalidContributedInput {
+				message: format!(
+					"{} outputs but {} receiver_owned flags were given; they must be the same length",
+					outputs.len(),
+					receiver_owned.len()
+				),
+			});
+		}
+		Ok(outputs
+			.into_iter()
+			.zip(receiver_owned)
+			.map(|(txout, is_receiver_owned)| self.contribute_output(txout, is_receiver_owned))
+			.collect())
+	}
+
+	/// The vouts of outputs contributed so far that belong to the receiver.
+	pub fn receiver_vouts(&self) -> Vec<u32> {
+		self.receiver_vouts.lock().unwrap().clone()
+	}
+
+	/// Route the surplus of `added_input_value_sat` over the value of outputs contributed via
+	/// `contribute_output(s)` to `change_vout`, a receiver-owned vout already contributed, then
+	/// move on to input contribution.
+	///
+	/// Returns a typed error, rather than panicking, when `added_input_value_sat` doesn't cover
+	/// the added outputs.
+	pub fn commit_outputs(
+		&self, added_input_value_sat: u64, change_vout: u32,
+	) -> Result<Arc<PayjoinProposal>, PayjoinError> {
+		let added_output_value_sat = *self.added_output_value_sat.lock().unwrap();
+		if added_input_value_sat < added_output_value_sat {
+			return Err(PayjoinError::InsufficientInputAmount {
+				message: format!(
+					"contributed input amount {added_input_value_sat} sat does not cover {added_output_value_sat} sat of added outputs"
+				),
+			});
+		}
+		let surplus = added_input_value_sat - added_output_value_sat;
+		if surplus > 0 && !self.receiver_vouts.lock().unwrap().contains(&change_vout) {
+			return Err(PayjoinError::InvalidContributedInput {
+				message: format!(
+					"change vout {change_vout} is not a receiver-owned output contributed via contribute_output(s)"
+				),
+			});
+		}
+		let mut guard = self.get_proposal_mutex_guard();
+		let proposal = guard.as_mut().unwrap();
+		if surplus > 0 {
+			let psbt = proposal.psbt_mut();
+			let change_output =
+				psbt.unsigned_tx.output.get_mut(change_vout as usize).ok_or_else(|| {
+					PayjoinError::ReceiveError {
+						message: format!("change vout {change_vout} not found on the proposal"),
+					}
+				})?;
+			change_output.value += payjoin::bitcoin::Amount::from_sat(surplus);
+		}
+		Ok(Arc::new(guard.take().unwrap().into()))
+	}
+}
+
 /// A mutable checked proposal that the receiver may contribute inputs to to make a payjoin.
 pub struct PayjoinProposal {
 	internal: Mutex<Option<PdkPayjoinProposal>>,
+	contributed_inputs: Mutex<HashSet<payjoin::bitcoin::OutPoint>>,
 }
 
 impl From<PdkPayjoinProposal> for PayjoinProposal {
 	fn from(value: PdkPayjoinProposal) -> Self {
-		PayjoinProposal { internal: Mutex::new(Some(value)) }
+		PayjoinProposal {
+			internal: Mutex::new(Some(value)),
+			contributed_inputs: Mutex::new(HashSet::new()),
+		}
 	}
 }
 
@@ -306,19 +428,93 @@ impl PayjoinProposal {
 		guard.as_mut().unwrap().is_output_substitution_disabled()
 	}
 
-	pub fn contribute_witness_input(&self, txout: TxOut, outpoint: OutPoint) {
+	/// Validate that none of `outpoints` are already contributed, nor repeated within this same
+	/// batch, then mark them all contributed. Validating the whole batch up front before mutating
+	/// the underlying proposal is what makes the contribution atomic: a bad input fails before
+	/// anything is applied, instead of leaving the proposal half-contributed.
+	fn mark_contributed_batch(&self, outpoints: &[OutPoint]) -> Result<(), PayjoinError> {
+		let mut contributed = self.contributed_inputs.lock().unwrap();
+		let mut seen_this_batch = HashSet::new();
+		for outpoint in outpoints {
+			let pdk_outpoint: payjoin::bitcoin::OutPoint = outpoint.to_owned().into();
+			if contributed.contains(&pdk_outpoint) || !seen_this_batch.insert(pdk_outpoint) {
+				return Err(PayjoinError::InvalidContributedInput {
+					message: format!(
+						"{}:{} was already contributed, or appears more than once in this batch",
+						outpoint.txid, outpoint.vout
+					),
+				});
+			}
+		}
+		contributed.extend(seen_this_batch);
+		Ok(())
+	}
+
+	pub fn contribute_witness_input(
+		&self, txout: TxOut, outpoint: OutPoint,
+	) -> Result<(), PayjoinError> {
+		self.contribute_witness_inputs(vec![(outpoint, txout)])
+	}
+
+	/// Contribute a batch of witness UTXOs in one call, validating and applying them atomically
+	/// so multi-input payjoins don't need a mutex round trip per input.
+	pub fn contribute_witness_inputs(
+		&self, inputs: Vec<(OutPoint, TxOut)>,
+	) -> Result<(), PayjoinError> {
+		let outpoints: Vec<OutPoint> = inputs.iter().map(|(outpoint, _)| outpoint.clone()).collect();
+		self.mark_contributed_batch(&outpoints)?;
 		let mut guard = self.get_proposal_mutex_guard();
-		guard.as_mut().unwrap().contribute_witness_input(txout.into(), outpoint.into());
+		let proposal = guard.as_mut().unwrap();
+		for (outpoint, txout) in inputs {
+			proposal.contribute_witness_input(txout.into(), outpoint.into());
+		}
+		Ok(())
 	}
 
-	pub fn contribute_non_witness_input(&self, tx: Arc<Transaction>, outpoint: OutPoint) {
+	pub fn contribute_non_witness_input(
+		&self, tx: Arc<Transaction>, outpoint: OutPoint,
+	) -> Result<(), PayjoinError> {
+		self.contribute_non_witness_inputs(vec![(outpoint, tx)])
+	}
+
+	/// Contribute a batch of non-witness (legacy) UTXOs in one call, validating and applying them
+	/// atomically so multi-input payjoins don't need a mutex round trip per input.
+	pub fn contribute_non_witness_inputs(
+		&self, inputs: Vec<(OutPoint, Arc<Transaction>)>,
+	) -> Result<(), PayjoinError> {
+		let outpoints: Vec<OutPoint> = inputs.iter().map(|(outpoint, _)| outpoint.clone()).collect();
+		self.mark_contributed_batch(&outpoints)?;
 		let mut guard = self.get_proposal_mutex_guard();
-		guard.as_mut().unwrap().contribute_non_witness_input((*tx).clone().into(), outpoint.into())
+		let proposal = guard.as_mut().unwrap();
+		for (outpoint, tx) in inputs {
+			proposal.contribute_non_witness_input((*tx).clone().into(), outpoint.into());
+		}
+		Ok(())
 	}
 
-	pub fn substitute_output_address(&self, substitute_address: Arc<Address>) {
+	/// The receiver-owned inputs committed to the proposal so far, so a wallet can lock exactly
+	/// these UTXOs against concurrent spends for the lifetime of the payjoin session.
+	pub fn utxos_to_be_locked(&self) -> Vec<OutPoint> {
+		self.contributed_inputs
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|outpoint| OutPoint { txid: outpoint.txid.to_string(), vout: outpoint.vout })
+			.collect()
+	}
+
+	pub fn substitute_output_address(
+		&self, substitute_address: Arc<Address>,
+	) -> Result<(), PayjoinError> {
 		let mut guard = self.get_proposal_mutex_guard();
-		guard.as_mut().unwrap().substitute_output_address((*substitute_address).clone().into())
+		let proposal = guard.as_mut().unwrap();
+		if proposal.is_output_substitution_disabled() {
+			return Err(PayjoinError::OutputSubstitutionDisabled {
+				message: "the sender set disableoutputsubstitution=true".to_string(),
+			});
+		}
+		proposal.substitute_output_address((*substitute_address).clone().into());
+		Ok(())
 	}
 
 	/// Apply additional fee contribution now that the receiver has contributed input this is kind of a “build_proposal” step before we sign and finalize and extract
@@ -330,7 +526,10 @@ impl PayjoinProposal {
 		let mut guard = self.get_proposal_mutex_guard();
 		match guard.as_mut().unwrap().apply_fee(min_feerate_sat_per_vb) {
 			Ok(e) => Ok(Arc::new(e.to_owned().into())),
-			Err(e) => Err(PayjoinError::RequestError { message: e.to_string() }),
+			// apply_fee's only failure mode is the computed fee exceeding the sender's
+			// maxadditionalfeecontribution, so the category is fixed by call site rather than by
+			// pattern-matching the opaque `payjoin` error's message text.
+			Err(e) => Err(PayjoinError::FeeTooHigh { message: e.to_string() }),
 		}
 	}
 
@@ -352,29 +551,79 @@ impl PayjoinProposal {
 		}
 	}
 
-	/// Select receiver input such that the payjoin avoids surveillance. Return the input chosen that has been applied to the Proposal.
+	/// Select a receiver input among `candidate_inputs` such that the payjoin avoids surveillance,
+	/// contribute it to the Proposal as a witness input, and return it.
 	///
-	/// Proper coin selection allows payjoin to resemble ordinary transactions. To ensure the resemblence, a number of heuristics must be avoided.
+	/// Proper coin selection allows payjoin to resemble ordinary transactions. To ensure the
+	/// resemblance, a number of heuristics must be avoided.
 	///
-	/// UIH “Unecessary input heuristic” is one class of them to avoid. We define UIH1 and UIH2 according to the BlockSci practice BlockSci UIH1 and UIH2:
+	/// UIH “Unnecessary input heuristic” is one class of them to avoid. We define UIH1 and UIH2
+	/// according to the BlockSci practice BlockSci UIH1 and UIH2. This iterates every candidate,
+	/// skips any that would trip the heuristic via `is_uih_avoided`, and contributes the first one
+	/// that doesn't. `candidate_inputs` takes the full `TxOut` (not just an amount) both because
+	/// contributing the input requires it and because keying candidates by amount alone would
+	/// silently drop same-valued UTXOs before they're ever evaluated. Callers with many candidate
+	/// UTXOs should filter with `is_uih_avoided` themselves first if they want more control over
+	/// which qualifying candidate is chosen.
 	pub fn try_preserving_privacy(
-		&self, candidate_inputs: HashMap<u64, OutPoint>,
+		&self, candidate_inputs: Vec<(OutPoint, TxOut)>,
 	) -> Result<OutPoint, PayjoinError> {
-		let mut _candidate_inputs: HashMap<payjoin::bitcoin::Amount, payjoin::bitcoin::OutPoint> =
-			HashMap::new();
-		for (key, value) in candidate_inputs.iter() {
-			_candidate_inputs.insert(
-				payjoin::bitcoin::Amount::from_sat(key.to_owned()),
-				value.to_owned().into(),
-			);
+		for (outpoint, txout) in candidate_inputs.into_iter() {
+			if self.is_uih_avoided(txout.value)? {
+				self.contribute_witness_input(txout, outpoint.clone())?;
+				return Ok(outpoint);
+			}
 		}
-		let mut guard = self.get_proposal_mutex_guard();
-		match guard.as_mut().unwrap().try_preserving_privacy(_candidate_inputs) {
-			Ok(e) => Ok(OutPoint { txid: e.txid.to_string(), vout: e.vout }),
-			Err(_) => Err(PayjoinError::SelectionError),
+		Err(PayjoinError::SelectionError)
+	}
+
+	/// Returns whether adding a receiver input worth `candidate_amount_sat` would avoid the
+	/// “unnecessary input heuristic” (UIH), so FFI callers can score their own candidates rather
+	/// than relying solely on `try_preserving_privacy`'s best-effort pick.
+	///
+	/// After the receiver adds a candidate input of value `v`, the transaction inputs are the
+	/// original inputs plus `v`. `min_out` is the smaller of the two output amounts (typically the
+	/// change) and `min_in` is the smallest input amount in the augmented transaction. The
+	/// heuristic classifies the tx as having an unnecessary input when `min_out < min_in` (UIH1);
+	/// otherwise UIH2. A payjoin looks most like an ordinary spend when adding the receiver input
+	/// does not flip the pattern, i.e. `min_out >= min_in`.
+	///
+	/// Only the 1-or-2-output case is considered safe to evaluate; a proposal with more outputs
+	/// returns `SelectionError` since the heuristic above no longer applies cleanly.
+	pub fn is_uih_avoided(&self, candidate_amount_sat: u64) -> Result<bool, PayjoinError> {
+		let guard = self.get_proposal_mutex_guard();
+		let proposal = guard.as_ref().unwrap();
+		let psbt = proposal.psbt();
+		if psbt.unsigned_tx.output.len() > 2 {
+			return Err(PayjoinError::SelectionError);
+		}
+		let min_out = psbt
+			.unsigned_tx
+			.output
+			.iter()
+			.map(|output| output.value)
+			.min()
+			.unwrap_or(payjoin::bitcoin::Amount::ZERO);
+		let min_original_in = (0..psbt.unsigned_tx.input.len())
+			.filter_map(|index| Self::input_amount(psbt, index))
+			.min()
+			.unwrap_or(payjoin::bitcoin::Amount::MAX_MONEY);
+		let candidate = payjoin::bitcoin::Amount::from_sat(candidate_amount_sat);
+		let min_in = std::cmp::min(min_original_in, candidate);
+		Ok(min_out >= min_in)
+	}
+
+	/// The spent amount of the PSBT input at `index`, looked up from its `witness_utxo` or, for
+	/// legacy inputs, its `non_witness_utxo`.
+	fn input_amount(psbt: &PdkPsbt, index: usize) -> Option<payjoin::bitcoin::Amount> {
+		let input = psbt.inputs.get(index)?;
+		if let Some(txout) = &input.witness_utxo {
+			return Some(txout.value);
 		}
+		let non_witness_utxo = input.non_witness_utxo.as_ref()?;
+		let vout = psbt.unsigned_tx.input.get(index)?.previous_output.vout as usize;
+		non_witness_utxo.output.get(vout).map(|txout| txout.value)
 	}
-	// TODO - pub fn utxos_to_be_locked(&self)
 }
 
 #[cfg(test)]
@@ -441,9 +690,93 @@ mod test {
 			.check_no_inputs_seen_before(Box::new(MockOutputOwned {}))
 			.expect("No inputs should be seen before")
 			.identify_receiver_outputs(Box::new(MockScriptOwned {}))
-			.expect("Receiver output should be identified");
+			.expect("Receiver output should be identified")
+			.commit_outputs(0, 0)
+			.expect("No outputs were added, nothing to commit");
 		let payjoin = payjoin.apply_fee(None);
 
 		assert!(payjoin.is_ok(), "Payjoin should be a valid PSBT");
 	}
+
+	/// Runs the test vector's Original PSBT through the checks up to (but not including)
+	/// `commit_outputs`, so tests can exercise `WantsOutputs` directly.
+	fn get_wants_outputs_from_test_vector() -> Arc<WantsOutputs> {
+		get_proposal_from_test_vector()
+			.unwrap()
+			.assume_interactive_receiver()
+			.clone()
+			.check_inputs_not_owned(Box::new(MockScriptOwned {}))
+			.expect("No inputs should be owned")
+			.check_no_mixed_input_scripts()
+			.expect("No mixed input scripts")
+			.check_no_inputs_seen_before(Box::new(MockOutputOwned {}))
+			.expect("No inputs should be seen before")
+			.identify_receiver_outputs(Box::new(MockScriptOwned {}))
+			.expect("Receiver output should be identified")
+	}
+
+	/// Runs the test vector's Original PSBT all the way to `PayjoinProposal`, contributing no
+	/// extra outputs along the way.
+	fn get_payjoin_proposal_from_test_vector() -> Arc<PayjoinProposal> {
+		get_wants_outputs_from_test_vector().commit_outputs(0, 0).expect("nothing to commit")
+	}
+
+	fn dummy_txout(value: u64) -> TxOut {
+		TxOut { value, script_pubkey: Arc::new(ScriptBuf { internal: payjoin::bitcoin::ScriptBuf::new() }) }
+	}
+
+	// The test vector's Original PSBT has one input worth 97_983_400 sat and two outputs worth
+	// 95_983_068 sat and 2_000_000 sat, so min_out = 2_000_000 and min_original_in = 97_983_400.
+	#[test]
+	fn is_uih_avoided_at_the_boundary() {
+		let payjoin = get_payjoin_proposal_from_test_vector();
+		assert!(
+			payjoin.is_uih_avoided(2_000_000).unwrap(),
+			"a candidate exactly matching min_out should not trip UIH"
+		);
+		assert!(
+			!payjoin.is_uih_avoided(2_000_001).unwrap(),
+			"a candidate larger than min_out becomes the new min_in and trips UIH1"
+		);
+	}
+
+	#[test]
+	fn commit_outputs_rejects_insufficient_input_amount() {
+		let wants_outputs = get_wants_outputs_from_test_vector();
+		let change_vout = wants_outputs.contribute_output(dummy_txout(10_000), true);
+		let result = wants_outputs.commit_outputs(5_000, change_vout);
+		assert!(
+			matches!(result, Err(PayjoinError::InsufficientInputAmount { .. })),
+			"contributing less than the added output value should be rejected, got {result:?}"
+		);
+	}
+
+	#[test]
+	fn commit_outputs_routes_surplus_to_change_vout() {
+		let wants_outputs = get_wants_outputs_from_test_vector();
+		let change_vout = wants_outputs.contribute_output(dummy_txout(10_000), true);
+		let payjoin = wants_outputs.commit_outputs(15_000, change_vout).expect("surplus covers output");
+		let mut guard = payjoin.get_proposal_mutex_guard();
+		let proposal = guard.as_mut().unwrap();
+		let change_output = &proposal.psbt_mut().unsigned_tx.output[change_vout as usize];
+		assert_eq!(
+			change_output.value,
+			payjoin::bitcoin::Amount::from_sat(15_000),
+			"the 5_000 sat surplus over the 10_000 sat output should land on the change vout"
+		);
+	}
+
+	#[test]
+	fn contribute_witness_inputs_rejects_duplicates() {
+		let payjoin = get_payjoin_proposal_from_test_vector();
+		let outpoint = OutPoint { txid: "0".repeat(64), vout: 0 };
+		let result = payjoin.contribute_witness_inputs(vec![
+			(outpoint.clone(), dummy_txout(1_000)),
+			(outpoint, dummy_txout(1_000)),
+		]);
+		assert!(
+			matches!(result, Err(PayjoinError::InvalidContributedInput { .. })),
+			"the same outpoint appearing twice in one batch should be rejected, got {result:?}"
+		);
+	}
 }